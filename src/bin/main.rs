@@ -1,9 +1,10 @@
 use clap::Parser;
+use color_eyre::eyre::ContextCompat;
 use color_eyre::Result;
 use config::Config;
 use rppal::gpio::Gpio;
 
-use d1flash::interface::{self, OpenDrainPin, OpenDrainState, Recipe};
+use d1flash::interface::{self, run_sequence_steps, OpenDrainPin, OpenDrainState, Recipe};
 
 fn main() -> Result<()> {
     let cli = interface::Cli::parse();
@@ -16,44 +17,38 @@ fn main() -> Result<()> {
         color_eyre::eyre::bail!("The default recipe does not match any of the given recipes.");
     }
 
+    let sequence_name = cli.sequence.as_ref().unwrap_or(&config.default_sequence);
+    let sequence = config
+        .sequences
+        .get(sequence_name)
+        .context(format!("No reset sequence named {sequence_name:?} is configured."))?;
+
     // Configure pins
     let gpio = Gpio::new()?;
     let mut boot = OpenDrainPin::new(
         gpio.get(config.boot.pin)?,
         OpenDrainState::Open,
+        config.boot.pull,
+        config.boot.drive,
+        config.boot.slew_rate,
         config.boot.state,
     );
     let mut reset = OpenDrainPin::new(
         gpio.get(config.reset.pin)?,
         OpenDrainState::Open,
+        config.reset.pull,
+        config.reset.drive,
+        config.reset.slew_rate,
         config.reset.state,
     );
 
     // Reboot ESP into flash mode. This is necessary for both flashing and monitoring
-    println!("Triggering boot mode pin (state: Low).");
-    boot.set_low();
-    std::thread::sleep(std::time::Duration::from_millis(20));
-
-    println!("Triggering reset pin (state: Low).");
-    reset.set_low();
-    std::thread::sleep(std::time::Duration::from_millis(100));
-
-    println!("Releasing reset pin (state: Open).");
-    reset.set_open();
-    std::thread::sleep(std::time::Duration::from_millis(100));
+    run_sequence_steps(&sequence.enter, &mut boot, &mut reset);
 
     std::thread::scope(|scope| {
         let reset_task = scope.spawn(|| {
-            if let Some(reset_flag) = cli.reset {
-                std::thread::sleep(std::time::Duration::from_millis(reset_flag.or(2000)));
-                boot.set_open();
-                println!("Triggering reset pin (state: Low).");
-                reset.set_low();
-                std::thread::sleep(std::time::Duration::from_millis(100));
-    
-                println!("Releasing reset pin (state: Open).");
-                reset.set_open();
-                std::thread::sleep(std::time::Duration::from_millis(100));
+            if cli.reset || cli.flash {
+                run_sequence_steps(&sequence.auto_reset, &mut boot, &mut reset);
             }
         });
 
@@ -72,18 +67,36 @@ fn main() -> Result<()> {
     });
 
     // Reboot ESP into normal mode, if flash mode was entered previously
-    println!("Releasing boot mode pin (state: Open).");
-    boot.set_open();
-    std::thread::sleep(std::time::Duration::from_millis(20));
+    run_sequence_steps(&sequence.exit, &mut boot, &mut reset);
 
-    println!("Triggering reset pin (state: Low).");
-    reset.set_low();
+    println!("Done!");
 
-    println!("Releasing reset pin (state: Open).");
-    std::thread::sleep(std::time::Duration::from_millis(100));
-    reset.set_open();
+    if cli.watch {
+        if let Some(wake) = &config.wake {
+            let mut wake_pin = gpio.get(wake.pin)?.into_input_pullup();
+            wake_pin.set_interrupt(wake.edge.into(), Some(wake.debounce()))?;
+            println!("Watching wake pin {} for {:?} edges.", wake.pin, wake.edge);
 
+            loop {
+                if wake_pin.poll_interrupt(true, None)?.is_some() {
+                    match config.recipes.get(&wake.recipe) {
+                        Some(recipe) => {
+                            println!("Wake pin triggered, executing {:?}.", recipe);
+                            if let Err(error) = recipe.execute() {
+                                eprintln!("Recipe {:?} failed: {error:?}", wake.recipe);
+                            }
+                        }
+                        None => eprintln!(
+                            "Wake pin triggered, but no recipe named {:?} is configured.",
+                            wake.recipe
+                        ),
+                    }
+                }
+            }
+        } else {
+            color_eyre::eyre::bail!("--watch was given, but no wake pin is configured.");
+        }
+    }
 
-    println!("Done!");
     Ok(())
 }
@@ -1,7 +1,7 @@
 use std::path::PathBuf;
 
 use clap::Parser;
-use color_eyre::eyre::{ContextCompat, Result};
+use color_eyre::eyre::{Context, ContextCompat, Result};
 use rppal::gpio::{IoPin, Pin};
 use serde::Deserialize;
 
@@ -98,7 +98,7 @@ impl From<rppal::gpio::Mode> for Mode {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
 pub enum OpenDrainState {
     Low,
     Open,
@@ -113,28 +113,156 @@ impl std::convert::From<OpenDrainState> for rppal::gpio::Mode {
     }
 }
 
+// Pull resistor applied while a FlexPin is configured as an input
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Pull {
+    None,
+    Up,
+    Down,
+}
+
+impl From<Pull> for rppal::gpio::PullUpDown {
+    fn from(value: Pull) -> Self {
+        match value {
+            Pull::None => Self::Off,
+            Pull::Up => Self::PullUp,
+            Pull::Down => Self::PullDown,
+        }
+    }
+}
+
+// Output drive strength. rppal doesn't expose the pad control registers, so this is recorded but not applied
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Drive {
+    Ma2,
+    Ma4,
+    Ma8,
+    Ma12,
+}
+
+// Output slew rate limiting. Same caveat as Drive
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum SlewRate {
+    Slow,
+    Fast,
+}
+
+// A pin reconfigurable at runtime as input, push-pull output, or simulated open-drain output
+#[derive(Debug)]
+pub struct FlexPin {
+    pin: IoPin,
+    // Last level commanded through `set_high`/`set_low`/`set_as_output_push_pull`/
+    // `set_as_open_drain`, for `StatefulOutputPin`. Not read back from the pin itself.
+    output_level: Level,
+}
+
+impl FlexPin {
+    pub fn new(pin: Pin) -> Self {
+        let mut pin = pin.into_io(rppal::gpio::Mode::Input);
+        pin.set_reset_on_drop(false);
+        Self {
+            pin,
+            output_level: Level::Low,
+        }
+    }
+
+    pub fn set_as_input(&mut self, pull: Pull) {
+        self.pin.set_mode(rppal::gpio::Mode::Input);
+        self.pin.set_pullupdown(pull.into());
+    }
+
+    pub fn set_as_output_push_pull(&mut self, level: Level) {
+        self.pin.set_mode(rppal::gpio::Mode::Output);
+        self.pin.write(level.into());
+        self.output_level = level;
+    }
+
+    pub fn set_as_open_drain(&mut self, state: OpenDrainState, pull: Pull) {
+        match state {
+            OpenDrainState::Low => {
+                // Ideally, we would like to set the logic level before changing the mode.
+                // It is not clear whether this works as intended, so we set it both before and after, just to make sure
+                self.pin.set_low();
+                self.pin.set_mode(rppal::gpio::Mode::Output);
+                self.pin.set_low();
+            }
+            OpenDrainState::Open => {
+                self.pin.set_mode(rppal::gpio::Mode::Input);
+                self.pin.set_pullupdown(pull.into());
+            }
+        }
+        self.output_level = match state {
+            OpenDrainState::Low => Level::Low,
+            OpenDrainState::Open => Level::High,
+        };
+    }
+
+    pub fn is_high(&self) -> bool {
+        self.pin.is_high()
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.pin.is_low()
+    }
+
+    pub fn set_high(&mut self) {
+        self.pin.set_high();
+        self.output_level = Level::High;
+    }
+
+    pub fn set_low(&mut self) {
+        self.pin.set_low();
+        self.output_level = Level::Low;
+    }
+}
+
 // Simulating open-drain pin configuration by switching between input and low output
 #[derive(Debug)]
 pub struct OpenDrainPin {
-    pin: IoPin,
+    pin: FlexPin,
     state: OpenDrainState,
+    pull: Pull,
+    // Recorded from the config, but not yet applied in software. See `Drive`/`SlewRate`.
+    #[allow(dead_code)]
+    drive: Option<Drive>,
+    #[allow(dead_code)]
+    slew_rate: Option<SlewRate>,
     initial_state: PinState,
     final_state: PinDropState,
 }
 
 impl OpenDrainPin {
-    pub fn new(pin: Pin, state: OpenDrainState, final_state: PinDropState) -> Self {
+    pub fn new(
+        pin: Pin,
+        state: OpenDrainState,
+        pull: Pull,
+        drive: Option<Drive>,
+        slew_rate: Option<SlewRate>,
+        final_state: PinDropState,
+    ) -> Self {
         // We disable the default drop behavior and handle it manually, such that the pin configuration can be maintained even after dropping
         let initial_state = PinState {
             mode: pin.mode().into(),
             level: pin.read().into(),
             pull: None, // Can't read pull up/down resistor configuration
         };
-        let mut pin = pin.into_io(state.into());
-        pin.set_reset_on_drop(false);
+
+        // rppal does not expose the BCM pad control registers, so these can't actually be
+        // applied. Warn instead of silently ignoring them, so users aren't misled into thinking
+        // their pin is driving stronger or slewing slower than it actually is.
+        if drive.is_some() || slew_rate.is_some() {
+            eprintln!(
+                "Warning: pin {} configures drive/slew_rate, but d1flash cannot apply these on this platform; they will be ignored.",
+                pin.pin()
+            );
+        }
+
         let mut pin = Self {
-            pin,
+            pin: FlexPin::new(pin),
             state,
+            pull,
+            drive,
+            slew_rate,
             initial_state,
             final_state,
         };
@@ -143,19 +271,12 @@ impl OpenDrainPin {
     }
 
     pub fn set_low(&mut self) {
-        // Ideally, we would like to set the logic level before changing the mode.
-        // It is not clear whether this works as intended, so we set it both before and after, just to make sure
-        self.pin.set_low();
-        self.pin.set_mode(rppal::gpio::Mode::Output);
-        self.pin.set_low();
-
+        self.pin.set_as_open_drain(OpenDrainState::Low, self.pull);
         self.state = OpenDrainState::Low;
     }
 
     pub fn set_open(&mut self) {
-        self.pin.set_mode(rppal::gpio::Mode::Input);
-        self.pin.set_pullupdown(rppal::gpio::PullUpDown::PullUp);
-
+        self.pin.set_as_open_drain(OpenDrainState::Open, self.pull);
         self.state = OpenDrainState::Open;
     }
 
@@ -165,24 +286,105 @@ impl OpenDrainPin {
             OpenDrainState::Open => self.set_open(),
         }
     }
+
+    pub fn is_high(&self) -> bool {
+        self.pin.is_high()
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.pin.is_low()
+    }
+}
+
+impl embedded_hal::digital::ErrorType for OpenDrainPin {
+    type Error = std::convert::Infallible;
+}
+
+impl embedded_hal::digital::OutputPin for OpenDrainPin {
+    // "High" for an open-drain pin means released, letting an external pull-up assert the line.
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.set_open();
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        OpenDrainPin::set_low(self);
+        Ok(())
+    }
+}
+
+impl embedded_hal::digital::StatefulOutputPin for OpenDrainPin {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.state == OpenDrainState::Open)
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.state == OpenDrainState::Low)
+    }
+}
+
+impl embedded_hal::digital::InputPin for OpenDrainPin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(OpenDrainPin::is_high(self))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(OpenDrainPin::is_low(self))
+    }
+}
+
+impl embedded_hal::digital::ErrorType for FlexPin {
+    type Error = std::convert::Infallible;
+}
+
+impl embedded_hal::digital::OutputPin for FlexPin {
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        FlexPin::set_high(self);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        FlexPin::set_low(self);
+        Ok(())
+    }
+}
+
+impl embedded_hal::digital::StatefulOutputPin for FlexPin {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(matches!(self.output_level, Level::High))
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(matches!(self.output_level, Level::Low))
+    }
+}
+
+impl embedded_hal::digital::InputPin for FlexPin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(FlexPin::is_high(self))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(FlexPin::is_low(self))
+    }
 }
 
 impl Drop for OpenDrainPin {
     fn drop(&mut self) {
-        self.pin.set_mode(
+        self.pin.pin.set_mode(
             self.final_state
                 .mode
                 .unwrap_or(self.initial_state.mode)
                 .into(),
         );
-        self.pin.write(
+        self.pin.pin.write(
             self.final_state
                 .level
                 .unwrap_or(self.initial_state.level)
                 .into(),
         );
         if let Some(pull) = self.final_state.pull {
-            self.pin.set_pullupdown(pull.into());
+            self.pin.pin.set_pullupdown(pull.into());
         }
     }
 }
@@ -206,20 +408,55 @@ pub struct PinDropState {
 pub struct PinConfig {
     pub pin: u8,
     pub state: PinDropState,
+    // Pull resistor applied while idling (open-drain "Open" / input state)
+    #[serde(default = "PinConfig::default_pull")]
+    pub pull: Pull,
+    pub drive: Option<Drive>,
+    pub slew_rate: Option<SlewRate>,
+}
+
+impl PinConfig {
+    fn default_pull() -> Pull {
+        Pull::Up
+    }
+}
+
+// Something to run once the boot/reset sequence has put the board into the right mode: an
+// external shell command, or (see SpiFlashRecipe) a direct SPI NOR flash program
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Recipe {
+    Command(CommandRecipe),
+    SpiFlash(SpiFlashRecipe),
+}
+
+impl Recipe {
+    pub fn execute(&self) -> Result<()> {
+        match self {
+            Recipe::Command(recipe) => recipe.execute(),
+            Recipe::SpiFlash(recipe) => recipe.execute(),
+        }
+    }
+}
+
+impl From<Vec<String>> for Recipe {
+    fn from(value: Vec<String>) -> Self {
+        Recipe::Command(CommandRecipe::from(value))
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
-pub struct Recipe {
+pub struct CommandRecipe {
     pub command: String,
     pub arguments: Vec<String>,
 }
 
-impl Recipe {
+impl CommandRecipe {
     pub fn new(command: String, arguments: Vec<String>) -> Self {
         Self { command, arguments }
     }
 
-    pub fn execute(&self) -> std::io::Result<std::process::ExitStatus> {
+    pub fn execute(&self) -> Result<()> {
     // pub fn execute(&self) -> std::io::Result<std::process::Child> {
         // std::process::Command::new(&self.command)
         //     .args(&self.arguments)
@@ -227,13 +464,18 @@ impl Recipe {
         //     .stderr(std::process::Stdio::piped())
         //     .spawn()
 
-        std::process::Command::new(&self.command)
+        let status = std::process::Command::new(&self.command)
             .args(&self.arguments)
-            .status()
+            .status()?;
+
+        status
+            .success()
+            .then_some(())
+            .context(format!("Command {:?} exited with {status}.", self.command))
     }
 }
 
-impl From<Vec<String>> for Recipe {
+impl From<Vec<String>> for CommandRecipe {
     fn from(value: Vec<String>) -> Self {
         let command = if value.is_empty() {
             String::new()
@@ -247,15 +489,370 @@ impl From<Vec<String>> for Recipe {
     }
 }
 
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum SpiBus {
+    Spi0,
+    Spi1,
+    Spi2,
+    Spi3,
+    Spi4,
+    Spi5,
+    Spi6,
+}
+
+impl From<SpiBus> for rppal::spi::Bus {
+    fn from(value: SpiBus) -> Self {
+        match value {
+            SpiBus::Spi0 => Self::Spi0,
+            SpiBus::Spi1 => Self::Spi1,
+            SpiBus::Spi2 => Self::Spi2,
+            SpiBus::Spi3 => Self::Spi3,
+            SpiBus::Spi4 => Self::Spi4,
+            SpiBus::Spi5 => Self::Spi5,
+            SpiBus::Spi6 => Self::Spi6,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum SpiSlaveSelect {
+    Ss0,
+    Ss1,
+    Ss2,
+}
+
+impl From<SpiSlaveSelect> for rppal::spi::SlaveSelect {
+    fn from(value: SpiSlaveSelect) -> Self {
+        match value {
+            SpiSlaveSelect::Ss0 => Self::Ss0,
+            SpiSlaveSelect::Ss1 => Self::Ss1,
+            SpiSlaveSelect::Ss2 => Self::Ss2,
+        }
+    }
+}
+
+const SPI_FLASH_CMD_JEDEC_ID: u8 = 0x9F;
+const SPI_FLASH_CMD_WRITE_ENABLE: u8 = 0x06;
+const SPI_FLASH_CMD_PAGE_PROGRAM: u8 = 0x02;
+const SPI_FLASH_CMD_SECTOR_ERASE: u8 = 0x20;
+const SPI_FLASH_CMD_READ_STATUS: u8 = 0x05;
+const SPI_FLASH_CMD_READ_DATA: u8 = 0x03;
+const SPI_FLASH_STATUS_WIP: u8 = 0x01;
+const SPI_FLASH_PAGE_SIZE: usize = 256;
+const SPI_FLASH_SECTOR_SIZE: usize = 4096;
+// Generous upper bound on JEDEC NOR sector-erase time (datasheets typically list ~400ms max, with
+// page-program well under that); bail out instead of hanging forever on a dead/miswired chip.
+const SPI_FLASH_BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(3000);
+
+// Programs an external SPI NOR flash directly while the MCU is held in reset via boot/reset
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpiFlashRecipe {
+    pub bus: SpiBus,
+    pub slave_select: SpiSlaveSelect,
+    pub clock_speed_hz: u32,
+    pub image_path: PathBuf,
+    pub offset: u32,
+}
+
+impl SpiFlashRecipe {
+    pub fn execute(&self) -> Result<()> {
+        let mut spi = rppal::spi::Spi::new(
+            self.bus.into(),
+            self.slave_select.into(),
+            self.clock_speed_hz,
+            rppal::spi::Mode::Mode0,
+        )?;
+
+        let jedec_id = self.read_jedec_id(&mut spi)?;
+        println!("Detected SPI flash JEDEC ID: {jedec_id:02x?}");
+
+        let image = std::fs::read(&self.image_path)
+            .with_context(|| format!("Failed to read firmware image {:?}.", self.image_path))?;
+
+        self.erase(&mut spi, image.len())?;
+        self.program(&mut spi, &image)?;
+        self.verify(&mut spi, &image)?;
+
+        Ok(())
+    }
+
+    fn read_jedec_id(&self, spi: &mut rppal::spi::Spi) -> Result<[u8; 3]> {
+        let mut id = [0u8; 3];
+        spi.transfer_segments(&[
+            rppal::spi::Segment::with_write(&[SPI_FLASH_CMD_JEDEC_ID]),
+            rppal::spi::Segment::with_read(&mut id),
+        ])?;
+        Ok(id)
+    }
+
+    fn write_enable(&self, spi: &mut rppal::spi::Spi) -> Result<()> {
+        spi.write(&[SPI_FLASH_CMD_WRITE_ENABLE])?;
+        Ok(())
+    }
+
+    fn wait_while_busy(&self, spi: &mut rppal::spi::Spi) -> Result<()> {
+        let deadline = std::time::Instant::now() + SPI_FLASH_BUSY_TIMEOUT;
+        loop {
+            let mut status = [0u8];
+            spi.transfer_segments(&[
+                rppal::spi::Segment::with_write(&[SPI_FLASH_CMD_READ_STATUS]),
+                rppal::spi::Segment::with_read(&mut status),
+            ])?;
+            if status[0] & SPI_FLASH_STATUS_WIP == 0 {
+                return Ok(());
+            }
+            if std::time::Instant::now() >= deadline {
+                color_eyre::eyre::bail!(
+                    "SPI flash did not clear its busy (WIP) bit within {SPI_FLASH_BUSY_TIMEOUT:?}."
+                );
+            }
+        }
+    }
+
+    fn address_command(&self, command: u8, address: u32) -> [u8; 4] {
+        [
+            command,
+            (address >> 16) as u8,
+            (address >> 8) as u8,
+            address as u8,
+        ]
+    }
+
+    fn erase(&self, spi: &mut rppal::spi::Spi, len: usize) -> Result<()> {
+        for address in erase_sector_addresses(self.offset, len, SPI_FLASH_SECTOR_SIZE) {
+            self.write_enable(spi)?;
+            spi.write(&self.address_command(SPI_FLASH_CMD_SECTOR_ERASE, address as u32))?;
+            self.wait_while_busy(spi)?;
+        }
+        Ok(())
+    }
+
+    fn program(&self, spi: &mut rppal::spi::Spi, image: &[u8]) -> Result<()> {
+        let mut written = 0;
+        for (address, chunk_len) in page_chunks(self.offset, image.len(), SPI_FLASH_PAGE_SIZE) {
+            let chunk = &image[written..written + chunk_len];
+
+            self.write_enable(spi)?;
+            let mut command = self
+                .address_command(SPI_FLASH_CMD_PAGE_PROGRAM, address as u32)
+                .to_vec();
+            command.extend_from_slice(chunk);
+            spi.write(&command)?;
+            self.wait_while_busy(spi)?;
+
+            written += chunk_len;
+        }
+        Ok(())
+    }
+
+    // Chunked the same way `program` is, since a single SPI transfer covering a whole firmware
+    // image is likely to exceed the kernel spidev driver's per-transfer buffer limit.
+    fn verify(&self, spi: &mut rppal::spi::Spi, image: &[u8]) -> Result<()> {
+        let mut checked = 0;
+        for (address, chunk_len) in page_chunks(self.offset, image.len(), SPI_FLASH_PAGE_SIZE) {
+            let mut readback = vec![0u8; chunk_len];
+            spi.transfer_segments(&[
+                rppal::spi::Segment::with_write(
+                    &self.address_command(SPI_FLASH_CMD_READ_DATA, address as u32),
+                ),
+                rppal::spi::Segment::with_read(&mut readback),
+            ])?;
+
+            (readback == image[checked..checked + chunk_len])
+                .then_some(())
+                .context(format!(
+                    "SPI flash verification failed: readback does not match the firmware image at offset {address:#x}."
+                ))?;
+
+            checked += chunk_len;
+        }
+        Ok(())
+    }
+}
+
+// Physical sector addresses an erase covering `offset..offset + len` must touch, aligned down/up
+// to `sector_size` since a sector-erase command always clears the whole sector it falls in.
+fn erase_sector_addresses(offset: u32, len: usize, sector_size: usize) -> Vec<usize> {
+    if len == 0 {
+        return Vec::new();
+    }
+
+    let start = offset as usize;
+    let end = start + len;
+    let aligned_start = start - (start % sector_size);
+    let aligned_end = end.div_ceil(sector_size) * sector_size;
+
+    (aligned_start..aligned_end).step_by(sector_size).collect()
+}
+
+// Splits `offset..offset + len` into `(address, chunk_len)` pairs that each stay within a single
+// `chunk_size`-aligned page, since a page-program command wraps instead of crossing into the next
+// page.
+fn page_chunks(offset: u32, len: usize, chunk_size: usize) -> Vec<(usize, usize)> {
+    let mut address = offset as usize;
+    let mut remaining = len;
+    let mut chunks = Vec::new();
+
+    while remaining > 0 {
+        let chunk_len = (chunk_size - address % chunk_size).min(remaining);
+        chunks.push((address, chunk_len));
+        address += chunk_len;
+        remaining -= chunk_len;
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod spi_flash_chunking_tests {
+    use super::*;
+
+    #[test]
+    fn erase_sectors_aligned_single_sector() {
+        assert_eq!(erase_sector_addresses(0, 10, 4096), vec![0]);
+    }
+
+    #[test]
+    fn erase_sectors_unaligned_offset() {
+        assert_eq!(erase_sector_addresses(0xFFE, 10, 4096), vec![0, 4096]);
+    }
+
+    #[test]
+    fn erase_sectors_spanning_multiple_sectors() {
+        assert_eq!(
+            erase_sector_addresses(4096, 4096 * 2 + 1, 4096),
+            vec![4096, 8192, 12288]
+        );
+    }
+
+    #[test]
+    fn erase_sectors_zero_length() {
+        assert_eq!(erase_sector_addresses(123, 0, 4096), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn page_chunks_aligned() {
+        assert_eq!(page_chunks(0, 512, 256), vec![(0, 256), (256, 256)]);
+    }
+
+    #[test]
+    fn page_chunks_unaligned_offset_wraps_at_page_boundary() {
+        // offset=16 into a 256-byte page, writing 256 bytes must stop 240 bytes in, then
+        // continue from the next page boundary instead of crossing it in one chunk.
+        assert_eq!(page_chunks(16, 256, 256), vec![(16, 240), (256, 16)]);
+    }
+
+    #[test]
+    fn page_chunks_near_page_boundary() {
+        assert_eq!(page_chunks(254, 4, 256), vec![(254, 2), (256, 2)]);
+    }
+
+    #[test]
+    fn page_chunks_zero_length() {
+        assert_eq!(page_chunks(0, 0, 256), Vec::<(usize, usize)>::new());
+    }
+}
+
 // Perform reset on drop and then set the Some settings afterwards
 #[derive(Debug, Deserialize)]
 // https://toml.io/en/
 pub struct Configuration {
     pub boot: PinConfig, // GPIO pin on the Raspberry Pi connected to D3 on the MCU (for boot configuration)
     pub reset: PinConfig, // GPIO pin on the Raspberry Pi connected to the reset pin on the MCU
-    // wake: u8, // GPIO pin on the Taspberry Pi used for waking it up from the MCU
+    pub wake: Option<WakeConfig>, // GPIO pin on the Raspberry Pi used for waking it up from the MCU
     pub default_recipe: String,
     pub recipes: std::collections::HashMap<String, Recipe>,
+    pub default_sequence: String,
+    pub sequences: std::collections::HashMap<String, ResetSequence>,
+}
+
+// Which of the two open-drain pins a SequenceStep drives
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum SequencePin {
+    Boot,
+    Reset,
+}
+
+// One step of a ResetSequence: drive a pin and hold, or just wait
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SequenceStep {
+    Drive {
+        pin: SequencePin,
+        state: OpenDrainState,
+        hold_ms: u64,
+    },
+    Wait {
+        hold_ms: u64,
+    },
+}
+
+impl SequenceStep {
+    pub fn run(&self, boot: &mut OpenDrainPin, reset: &mut OpenDrainPin) {
+        match *self {
+            SequenceStep::Drive { pin, state, hold_ms } => {
+                println!("Triggering {pin:?} pin (state: {state:?}).");
+                let pin = match pin {
+                    SequencePin::Boot => boot,
+                    SequencePin::Reset => reset,
+                };
+                pin.set(state);
+                std::thread::sleep(std::time::Duration::from_millis(hold_ms));
+            }
+            SequenceStep::Wait { hold_ms } => {
+                std::thread::sleep(std::time::Duration::from_millis(hold_ms));
+            }
+        }
+    }
+}
+
+// A named boot/reset timing preset (e.g. classic, esp32c3, usb-jtag). auto_reset runs in the
+// background while a recipe executes if --reset/--flash was given
+#[derive(Clone, Debug, Deserialize)]
+pub struct ResetSequence {
+    pub enter: Vec<SequenceStep>,
+    pub auto_reset: Vec<SequenceStep>,
+    pub exit: Vec<SequenceStep>,
+}
+
+// Runs each step of `steps` in order against boot/reset
+pub fn run_sequence_steps(steps: &[SequenceStep], boot: &mut OpenDrainPin, reset: &mut OpenDrainPin) {
+    for step in steps {
+        step.run(boot, reset);
+    }
+}
+
+// Which edge(s) of the wake pin trigger its recipe
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Edge {
+    Rising,
+    Falling,
+    Both,
+}
+
+impl From<Edge> for rppal::gpio::Trigger {
+    fn from(value: Edge) -> Self {
+        match value {
+            Edge::Rising => Self::RisingEdge,
+            Edge::Falling => Self::FallingEdge,
+            Edge::Both => Self::Both,
+        }
+    }
+}
+
+// Wake pin the MCU uses to signal the Pi. With --watch, runs `recipe` on each debounced edge
+#[derive(Debug, Deserialize)]
+pub struct WakeConfig {
+    pub pin: u8,
+    pub edge: Edge,
+    pub debounce_ms: u64,
+    pub recipe: String,
+}
+
+impl WakeConfig {
+    pub fn debounce(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.debounce_ms)
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -280,7 +877,17 @@ pub struct Cli {
     /// Whether or not the ESP should be rebooted into flash mode.
     /// flash implies reset.
     #[arg(short, long)]
-    pub flash: bool
+    pub flash: bool,
+
+    /// Keep running after the main sequence, re-arming the configured wake pin and running its
+    /// recipe each time the MCU signals it, until interrupted.
+    #[arg(short, long)]
+    pub watch: bool,
+
+    /// The named reset/boot timing sequence to use.
+    /// If not specified, the configuration's default_sequence is used.
+    #[arg(short, long, value_name = "NAME")]
+    pub sequence: Option<String>,
 }
 
 fn valid_path(path: &str) -> Result<PathBuf, color_eyre::Report> {